@@ -24,125 +24,437 @@
 //! # }
 //! ```
 //!
-//! # Features
+//! # Destinations
 //!
-//! This crate provides a single feature, `big_data`, which enables large amounts of data
-//! to be encoded by prepending the length of the data to the data itself,
-//! using tokio's `LengthDelimitedCodec`.
+//! `BinCodec` is generic over a `Destination`, which decides how one message
+//! is told apart from the next on the wire. [`SyncDestination`] (the
+//! default) relies on bincode's own encoding being self-describing and reads
+//! until a full message has been deserialized. [`AsyncDestination`] instead
+//! prepends each message with a 4-byte length header, so a reader knows how
+//! many bytes to buffer before it even attempts to deserialize. Pick
+//! `AsyncDestination` with [`BinCodec::for_async`] when talking to a peer
+//! that expects size-prefixed frames; the two can be mixed freely across
+//! connections in the same process.
 //!
-//! This functionality is optional because it might affect performance.
+//! # Asymmetric protocols
+//!
+//! `BinCodec<Dec, Enc>` takes a second, independent item type for
+//! encoding, defaulting to the decode type for backward compatibility. This
+//! is useful for request/response protocols where the two directions carry
+//! different types, e.g. `BinCodec::<Response, Request>::new()` on a client
+//! connection.
+//!
+//! # Chunked framing
+//!
+//! [`ChunkedDestination`] splits a message into a sequence of bounded-size
+//! chunks instead of one contiguous frame, which keeps peak buffer usage low
+//! when very large values are sent. Pick it with [`BinCodec::for_chunked`]
+//! and tune the chunk size with [`BinCodec::chunk_size`] (16 KiB default).
 
 #![deny(missing_docs, missing_debug_implementations)]
 
 use bincode::Config;
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use serde::{Deserialize, Serialize};
-use std::{fmt, marker::PhantomData};
+use std::{collections::VecDeque, fmt, marker::PhantomData};
 use tokio::codec::{Decoder, Encoder};
 
-#[cfg(feature = "big_data")]
-use tokio::codec::length_delimited::{Builder, LengthDelimitedCodec};
+/// Decides how messages are delimited on the wire.
+///
+/// See the [crate-level documentation](index.html#destinations) for the
+/// difference between [`SyncDestination`], [`AsyncDestination`] and
+/// [`ChunkedDestination`].
+pub trait Destination: fmt::Debug {}
+
+/// Frames messages with no explicit length prefix, relying on bincode's
+/// self-describing encoding to know where one message ends and the next
+/// begins.
+///
+/// This is the default destination used by [`BinCodec`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SyncDestination;
 
-/// Bincode based codec for use with `tokio-codec`
+impl Destination for SyncDestination {}
+
+/// Frames each message with a leading 4-byte, network-endian length prefix.
 ///
-/// # Note
+/// Useful when the peer needs to know how many bytes to buffer before a
+/// message can be deserialized, such as when buffering on a separate task
+/// from the one doing the deserializing.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AsyncDestination;
+
+impl Destination for AsyncDestination {}
+
+/// Frames a message as a sequence of bounded-size chunks, each carrying a
+/// small header (message id, chunk index, final-chunk flag), so that
+/// encoding and decoding very large values never requires holding the whole
+/// serialized payload in one contiguous frame on the wire at once.
 ///
-/// Optionally depends on [`LengthDelimitedCodec`](https://docs.rs/tokio/0.1/tokio/codec/length_delimited/struct.LengthDelimitedCodec.html)
-/// when `big_data` feature is enabled
-pub struct BinCodec<T> {
-    #[cfg(feature = "big_data")]
-    lower: LengthDelimitedCodec,
+/// The decoder accumulates chunks into a deque of [`Bytes`] and only joins
+/// them into one contiguous buffer once the final chunk has arrived, right
+/// before handing them to bincode.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ChunkedDestination;
+
+impl Destination for ChunkedDestination {}
+
+/// The capacity the decode buffer is kept reserved to, so that steady-state
+/// decoding does not repeatedly reallocate as messages are split off of it.
+const DEFAULT_RESERVE: usize = 8 * 1024;
+
+/// Default size, in bytes, of each chunk emitted by [`ChunkedDestination`].
+const DEFAULT_CHUNK_SIZE: usize = 16 * 1024;
+
+/// Size, in bytes, of a chunk header: a `u32` message id, a `u32` chunk
+/// index, a `u8` final-chunk flag, and a `u32` chunk payload length.
+const CHUNK_HEADER_LEN: usize = 4 + 4 + 1 + 4;
+
+fn read_u32(buf: &[u8]) -> u32 {
+    u32::from(buf[0]) << 24 | u32::from(buf[1]) << 16 | u32::from(buf[2]) << 8 | u32::from(buf[3])
+}
+
+/// Bincode based codec for use with `tokio-codec`
+///
+/// `Dec` is the type produced by [`Decoder::decode`]; `Enc` is the type
+/// accepted by [`Encoder::encode`] and defaults to `Dec`, so `BinCodec<T>` is
+/// still a codec that reads and writes the same type `T`.
+pub struct BinCodec<Dec, Enc = Dec, D = SyncDestination> {
     config: Config,
-    _pd: PhantomData<T>,
+    max_frame_length: Option<usize>,
+    chunk_size: usize,
+    next_message_id: u32,
+    assembling: Option<(u32, VecDeque<Bytes>, usize)>,
+    _pd: PhantomData<(Dec, Enc, D)>,
 }
 
-impl<T> BinCodec<T> {
+impl<Dec, Enc, D: Destination> BinCodec<Dec, Enc, D> {
     /// Provides a bincode based codec
-    pub fn new() -> Self { Self::default() }
+    pub fn new() -> Self
+    where
+        Self: Default,
+    {
+        Self::default()
+    }
 
     /// Provides a bincode based codec from the bincode config
-    #[cfg(not(feature = "big_data"))]
-    pub fn with_config(config: Config) -> Self { BinCodec { config, _pd: PhantomData } }
+    pub fn with_config(config: Config) -> Self {
+        BinCodec {
+            config,
+            max_frame_length: None,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            next_message_id: 0,
+            assembling: None,
+            _pd: PhantomData,
+        }
+    }
+
+    /// Rejects any single message whose length exceeds `max`, returning an
+    /// error from `decode` instead of attempting to allocate or deserialize
+    /// it.
+    ///
+    /// Under [`AsyncDestination`] and [`ChunkedDestination`], `max` is
+    /// checked against the length header before buffering its payload, so a
+    /// hostile header never causes a large reservation. Under
+    /// [`SyncDestination`], where no such header exists, `max` is instead
+    /// enforced by bincode itself while it deserializes: a message whose
+    /// *encoded* length exceeds `max` is rejected as soon as bincode reads
+    /// enough to know that, rather than being allocated first. Either way,
+    /// this bounds a single message, not how many complete messages happen
+    /// to be buffered together.
+    ///
+    /// Unset by default, i.e. messages of any length are accepted.
+    pub fn max_frame_length(mut self, max: usize) -> Self {
+        self.max_frame_length = Some(max);
+        self.config.limit(max as u64);
+        self
+    }
 
-    /// Provides a bincode based codec from the bincode config and a `LengthDelimitedCodec` builder
-    #[cfg(feature = "big_data")]
-    pub fn with_config(config: Config, builder: &mut Builder) -> Self {
-        BinCodec { lower: builder.new_codec(), config, _pd: PhantomData }
+    /// Sets the size, in bytes, of each chunk emitted under
+    /// [`ChunkedDestination`]. Has no effect for other destinations.
+    ///
+    /// Defaults to 16 KiB.
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        self.chunk_size = size;
+        self
+    }
+
+    /// Switches this codec to the [`AsyncDestination`] framing, which
+    /// prepends each message with a 4-byte length prefix.
+    pub fn for_async(self) -> BinCodec<Dec, Enc, AsyncDestination> {
+        BinCodec {
+            config: self.config,
+            max_frame_length: self.max_frame_length,
+            chunk_size: self.chunk_size,
+            next_message_id: 0,
+            assembling: None,
+            _pd: PhantomData,
+        }
+    }
+
+    /// Switches this codec to the [`SyncDestination`] framing, which relies
+    /// on bincode's self-describing encoding alone.
+    pub fn for_sync(self) -> BinCodec<Dec, Enc, SyncDestination> {
+        BinCodec {
+            config: self.config,
+            max_frame_length: self.max_frame_length,
+            chunk_size: self.chunk_size,
+            next_message_id: 0,
+            assembling: None,
+            _pd: PhantomData,
+        }
+    }
+
+    /// Switches this codec to the [`ChunkedDestination`] framing, which
+    /// splits large messages into a sequence of bounded-size chunks.
+    pub fn for_chunked(self) -> BinCodec<Dec, Enc, ChunkedDestination> {
+        BinCodec {
+            config: self.config,
+            max_frame_length: self.max_frame_length,
+            chunk_size: self.chunk_size,
+            next_message_id: 0,
+            assembling: None,
+            _pd: PhantomData,
+        }
     }
 }
 
-impl<T> Default for BinCodec<T> {
+impl<Dec, Enc, D: Destination> Default for BinCodec<Dec, Enc, D> {
     #[inline]
-    fn default() -> Self {
-        let config = bincode::config();
-        BinCodec::with_config(
-            config,
-            #[cfg(feature = "big_data")]
-            &mut Builder::new(),
-        )
-    }
+    fn default() -> Self { BinCodec::with_config(bincode::config()) }
+}
+
+fn frame_too_large(len: usize, max: usize) -> bincode::Error {
+    Box::new(bincode::ErrorKind::Custom(format!(
+        "frame of length {} exceeds max_frame_length of {}",
+        len, max
+    )))
 }
 
-impl<T> Decoder for BinCodec<T>
+impl<Dec, Enc> Decoder for BinCodec<Dec, Enc, SyncDestination>
 where
-    for<'de> T: Deserialize<'de>,
+    for<'de> Dec: Deserialize<'de>,
 {
     type Error = bincode::Error;
-    type Item = T;
-
-    #[cfg(feature = "big_data")]
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        Ok(if let Some(buf) = self.lower.decode(src)? {
-            Some(self.config.deserialize(&buf)?)
-        } else {
-            None
-        })
+    type Item = Dec;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if buf.is_empty() {
+            buf.reserve(DEFAULT_RESERVE);
+            return Ok(None);
+        }
+
+        let mut reader = reader::Reader::new(&buf[..]);
+        match self.config.deserialize_from(&mut reader) {
+            Ok(message) => {
+                let amount = reader.amount();
+                buf.split_to(amount);
+                if buf.capacity() - buf.len() < DEFAULT_RESERVE {
+                    buf.reserve(DEFAULT_RESERVE);
+                }
+                Ok(Some(message))
+            }
+            // Bincode's self-describing encoding carries no length prefix,
+            // so running out of buffered bytes partway through a message
+            // looks just like any other `Read` hitting EOF. That alone
+            // isn't an error: more bytes may simply not have arrived yet.
+            // `max_frame_length`, when set, is enforced by
+            // bincode's own size limit (see `Config::limit` in
+            // `max_frame_length` above) as it deserializes, so a message
+            // that is merely incomplete is never confused with one that is
+            // actually oversized.
+            Err(ref e) if needs_more_data(e) => {
+                buf.reserve(DEFAULT_RESERVE);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
     }
+}
+
+/// Whether `err` looks like a `Read` simply running out of buffered bytes
+/// partway through a message, rather than a genuine decode failure.
+fn needs_more_data(err: &bincode::Error) -> bool {
+    match &**err {
+        bincode::ErrorKind::Io(io_err) => io_err.kind() == std::io::ErrorKind::UnexpectedEof,
+        _ => false,
+    }
+}
+
+impl<Dec, Enc> Decoder for BinCodec<Dec, Enc, AsyncDestination>
+where
+    for<'de> Dec: Deserialize<'de>,
+{
+    type Error = bincode::Error;
+    type Item = Dec;
 
-    #[cfg(not(feature = "big_data"))]
     fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        if !buf.is_empty() {
-            let mut reader = reader::Reader::new(&buf[..]);
-            let message = self.config.deserialize_from(&mut reader)?;
-            let amount = reader.amount();
-            buf.split_to(amount);
-            Ok(Some(message))
-        } else {
-            Ok(None)
+        if buf.len() < 4 {
+            buf.reserve(DEFAULT_RESERVE);
+            return Ok(None);
         }
+
+        let size = read_u32(&buf[..4]) as usize;
+
+        if let Some(max) = self.max_frame_length {
+            if size > max {
+                return Err(frame_too_large(size, max));
+            }
+        }
+
+        if buf.len() < 4 + size {
+            buf.reserve(4 + size - buf.len());
+            return Ok(None);
+        }
+
+        buf.split_to(4);
+        let message = buf.split_to(size);
+        if buf.capacity() - buf.len() < DEFAULT_RESERVE {
+            buf.reserve(DEFAULT_RESERVE);
+        }
+        Ok(Some(self.config.deserialize(&message)?))
     }
 }
 
-impl<T> Encoder for BinCodec<T>
+impl<Dec, Enc> Encoder for BinCodec<Dec, Enc, SyncDestination>
 where
-    T: Serialize,
+    Enc: Serialize,
 {
     type Error = bincode::Error;
-    type Item = T;
+    type Item = Enc;
 
-    #[cfg(feature = "big_data")]
-    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let bytes = self.config.serialize(&item)?;
-        self.lower.encode(bytes.into(), dst)?;
+    fn encode(&mut self, item: Enc, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut writer = writer::Writer::new(buf);
+        self.config.serialize_into(&mut writer, &item)?;
         Ok(())
     }
+}
 
-    #[cfg(not(feature = "big_data"))]
-    fn encode(&mut self, item: T, buf: &mut BytesMut) -> Result<(), Self::Error> {
+impl<Dec, Enc> Encoder for BinCodec<Dec, Enc, AsyncDestination>
+where
+    Enc: Serialize,
+{
+    type Error = bincode::Error;
+    type Item = Enc;
+
+    fn encode(&mut self, item: Enc, buf: &mut BytesMut) -> Result<(), Self::Error> {
         use bytes::BufMut;
-        let size = self.config.serialized_size(&item)?;
-        buf.reserve(size as usize);
-        let message = self.config.serialize(&item)?;
-        buf.put(&message[..]);
+
+        let size = self.config.serialized_size(&item)? as usize;
+        buf.reserve(4 + size);
+        buf.put_u32_be(size as u32);
+
+        let mut writer = writer::Writer::new(buf);
+        self.config.serialize_into(&mut writer, &item)?;
+        Ok(())
+    }
+}
+
+impl<Dec, Enc> Decoder for BinCodec<Dec, Enc, ChunkedDestination>
+where
+    for<'de> Dec: Deserialize<'de>,
+{
+    type Error = bincode::Error;
+    type Item = Dec;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            if buf.len() < CHUNK_HEADER_LEN {
+                buf.reserve(DEFAULT_RESERVE);
+                return Ok(None);
+            }
+
+            let message_id = read_u32(&buf[0..4]);
+            let chunk_index = read_u32(&buf[4..8]);
+            let is_final = buf[8] != 0;
+            let payload_len = read_u32(&buf[9..13]) as usize;
+
+            if let Some(max) = self.max_frame_length {
+                if payload_len > max {
+                    return Err(frame_too_large(payload_len, max));
+                }
+            }
+
+            if buf.len() < CHUNK_HEADER_LEN + payload_len {
+                buf.reserve(CHUNK_HEADER_LEN + payload_len - buf.len());
+                return Ok(None);
+            }
+
+            buf.split_to(CHUNK_HEADER_LEN);
+            let chunk = buf.split_to(payload_len).freeze();
+            if buf.capacity() - buf.len() < DEFAULT_RESERVE {
+                buf.reserve(DEFAULT_RESERVE);
+            }
+
+            // A chunk from a new message arriving before the previous one
+            // finished means the previous one was abandoned; start over.
+            if self.assembling.as_ref().map(|(id, ..)| *id) != Some(message_id) {
+                if chunk_index != 0 {
+                    return Err(Box::new(bincode::ErrorKind::Custom(format!(
+                        "received chunk {} of unknown message {}",
+                        chunk_index, message_id
+                    ))));
+                }
+                self.assembling = Some((message_id, VecDeque::new(), 0));
+            }
+
+            let (_, parts, assembled_len) = self.assembling.as_mut().unwrap();
+
+            if chunk_index != parts.len() as u32 {
+                self.assembling = None;
+                return Err(Box::new(bincode::ErrorKind::Custom(format!(
+                    "received out-of-order chunk {} for message {}, expected {}",
+                    chunk_index,
+                    message_id,
+                    parts.len()
+                ))));
+            }
+
+            *assembled_len += chunk.len();
+            if let Some(max) = self.max_frame_length {
+                if *assembled_len > max {
+                    self.assembling = None;
+                    return Err(frame_too_large(*assembled_len, max));
+                }
+            }
+            parts.push_back(chunk);
+
+            if is_final {
+                let (_, parts, _) = self.assembling.take().unwrap();
+                let total = parts.iter().map(Bytes::len).sum();
+                let mut assembled = BytesMut::with_capacity(total);
+                for part in parts {
+                    assembled.extend_from_slice(&part);
+                }
+                return Ok(Some(self.config.deserialize(&assembled)?));
+            }
+        }
+    }
+}
+
+impl<Dec, Enc> Encoder for BinCodec<Dec, Enc, ChunkedDestination>
+where
+    Enc: Serialize,
+{
+    type Error = bincode::Error;
+    type Item = Enc;
+
+    fn encode(&mut self, item: Enc, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        let message_id = self.next_message_id;
+        self.next_message_id = self.next_message_id.wrapping_add(1);
+        let chunk_size = self.chunk_size.max(1);
+
+        let mut writer = chunk_writer::ChunkWriter::new(buf, chunk_size, message_id);
+        self.config.serialize_into(&mut writer, &item)?;
+        writer.finish();
         Ok(())
     }
 }
 
-impl<T> fmt::Debug for BinCodec<T> {
+impl<Dec, Enc, D: Destination> fmt::Debug for BinCodec<Dec, Enc, D> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.debug_struct("BinCodec").finish() }
 }
 
-#[cfg(not(feature = "big_data"))]
 mod reader {
     use tokio::{io, prelude::Read};
 
@@ -167,6 +479,104 @@ mod reader {
     }
 }
 
+/// Writes serialized bytes straight into a destination `BytesMut`, growing it
+/// on demand.
+///
+/// This lets `bincode` serialize directly into the frame buffer instead of
+/// into an intermediate `Vec<u8>` that then has to be copied in, mirroring
+/// how [`reader::Reader`] lets `bincode` deserialize straight out of the
+/// frame buffer.
+mod writer {
+    use bytes::{BufMut, BytesMut};
+    use tokio::{io, prelude::Write};
+
+    #[derive(Debug)]
+    pub struct Writer<'buf> {
+        buf: &'buf mut BytesMut,
+    }
+
+    impl<'buf> Writer<'buf> {
+        pub fn new(buf: &'buf mut BytesMut) -> Self { Writer { buf } }
+    }
+
+    impl<'buf> Write for Writer<'buf> {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.buf.reserve(bytes.len());
+            self.buf.put(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+}
+
+/// Writes serialized bytes out as a sequence of [`ChunkedDestination`]
+/// frames, flushing a chunk to the destination `BytesMut` as soon as
+/// `chunk_size` bytes have been buffered, instead of buffering the whole
+/// serialized value before splitting it up. This keeps the extra memory this
+/// writer holds onto bounded by `chunk_size`, no matter how large the value
+/// being serialized is.
+mod chunk_writer {
+    use super::CHUNK_HEADER_LEN;
+    use bytes::{BufMut, BytesMut};
+    use tokio::{io, prelude::Write};
+
+    #[derive(Debug)]
+    pub struct ChunkWriter<'buf> {
+        dst: &'buf mut BytesMut,
+        chunk_size: usize,
+        message_id: u32,
+        chunk_index: u32,
+        pending: BytesMut,
+    }
+
+    impl<'buf> ChunkWriter<'buf> {
+        pub fn new(dst: &'buf mut BytesMut, chunk_size: usize, message_id: u32) -> Self {
+            ChunkWriter {
+                dst,
+                chunk_size,
+                message_id,
+                chunk_index: 0,
+                pending: BytesMut::with_capacity(chunk_size),
+            }
+        }
+
+        fn flush_chunk(&mut self, is_final: bool) {
+            self.dst.reserve(CHUNK_HEADER_LEN + self.pending.len());
+            self.dst.put_u32_be(self.message_id);
+            self.dst.put_u32_be(self.chunk_index);
+            self.dst.put_u8(is_final as u8);
+            self.dst.put_u32_be(self.pending.len() as u32);
+            self.dst.put(&self.pending[..]);
+            self.pending.clear();
+            self.chunk_index += 1;
+        }
+
+        /// Flushes the last, possibly partial or empty, chunk as the final
+        /// one. Must be called exactly once after all of a message's bytes
+        /// have been written.
+        pub fn finish(mut self) { self.flush_chunk(true); }
+    }
+
+    impl<'buf> Write for ChunkWriter<'buf> {
+        fn write(&mut self, mut bytes: &[u8]) -> io::Result<usize> {
+            let written = bytes.len();
+            while !bytes.is_empty() {
+                let space = self.chunk_size - self.pending.len();
+                let take = space.min(bytes.len());
+                self.pending.put(&bytes[..take]);
+                bytes = &bytes[take..];
+                if self.pending.len() == self.chunk_size {
+                    self.flush_chunk(false);
+                }
+            }
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -238,8 +648,7 @@ mod tests {
     }
 
     #[test]
-    #[cfg(feature = "big_data")]
-    fn big_data() {
+    fn async_destination() {
         #[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
         enum Mock {
             One(Vec<u8>),
@@ -247,10 +656,118 @@ mod tests {
         }
 
         let addr = SocketAddr::new("127.0.0.1".parse().unwrap(), 15152);
-        let jh = start_server::<Mock>(addr);
+        let echo = TcpListener::bind(&addr).unwrap();
+        let jh = std::thread::spawn(move || {
+            current_thread::run(
+                echo.incoming()
+                    .map_err(bincode::Error::from)
+                    .take(1)
+                    .for_each(|stream| {
+                        let (w, r) =
+                            Framed::new(stream, BinCodec::<Mock>::new().for_async()).split();
+                        r.forward(w).map(|_| ())
+                    })
+                    .map_err(|_| ()),
+            )
+        });
 
         let client = TcpStream::connect(&addr).wait().unwrap();
-        let client = Framed::new(client, BinCodec::<Mock>::new());
+        let client = Framed::new(client, BinCodec::<Mock>::new().for_async());
+        let data = Mock::One(vec![0; 1_000_000]);
+        let client = client.send(data.clone()).wait().unwrap();
+
+        let (got, client) = match client.into_future().wait() {
+            Ok(x) => x,
+            Err((e, _)) => panic!("[Mock::One]> Error during deserialize: {:?}", e),
+        };
+
+        assert_eq!(got, Some(data));
+
+        let data = Mock::Two;
+        let client = client.send(data.clone()).wait().unwrap();
+
+        let (got, client) = match client.into_future().wait() {
+            Ok(x) => x,
+            Err((e, _)) => panic!("[Mock::Two]> Error during deserialize: {:?}", e),
+        };
+
+        assert_eq!(got, Some(data));
+
+        drop(client);
+        jh.join().unwrap();
+    }
+
+    #[test]
+    fn asymmetric_types() {
+        #[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+        enum Request {
+            Ping,
+        }
+
+        #[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+        enum Response {
+            Pong,
+        }
+
+        let addr = SocketAddr::new("127.0.0.1".parse().unwrap(), 15153);
+        let echo = TcpListener::bind(&addr).unwrap();
+        let jh = std::thread::spawn(move || {
+            current_thread::run(
+                echo.incoming()
+                    .map_err(bincode::Error::from)
+                    .take(1)
+                    .for_each(|stream| {
+                        let (w, r) =
+                            Framed::new(stream, BinCodec::<Request, Response>::new()).split();
+                        r.map(|_| Response::Pong).forward(w).map(|_| ())
+                    })
+                    .map_err(|_| ()),
+            )
+        });
+
+        let client = TcpStream::connect(&addr).wait().unwrap();
+        let client = Framed::new(client, BinCodec::<Response, Request>::new());
+
+        let client = client.send(Request::Ping).wait().unwrap();
+
+        let (got, client) = match client.into_future().wait() {
+            Ok(x) => x,
+            Err((e, _)) => panic!("[Request::Ping]> Error during deserialize: {:?}", e),
+        };
+
+        assert_eq!(got, Some(Response::Pong));
+
+        drop(client);
+        jh.join().unwrap();
+    }
+
+    #[test]
+    fn chunked_destination() {
+        #[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+        enum Mock {
+            One(Vec<u8>),
+            Two,
+        }
+
+        let addr = SocketAddr::new("127.0.0.1".parse().unwrap(), 15154);
+        let echo = TcpListener::bind(&addr).unwrap();
+        let jh = std::thread::spawn(move || {
+            current_thread::run(
+                echo.incoming()
+                    .map_err(bincode::Error::from)
+                    .take(1)
+                    .for_each(|stream| {
+                        let codec = BinCodec::<Mock>::new().for_chunked().chunk_size(16);
+                        let (w, r) = Framed::new(stream, codec).split();
+                        r.forward(w).map(|_| ())
+                    })
+                    .map_err(|_| ()),
+            )
+        });
+
+        let client = TcpStream::connect(&addr).wait().unwrap();
+        let codec = BinCodec::<Mock>::new().for_chunked().chunk_size(16);
+        let client = Framed::new(client, codec);
         let data = Mock::One(vec![0; 1_000_000]);
         let client = client.send(data.clone()).wait().unwrap();
 
@@ -274,4 +791,71 @@ mod tests {
         drop(client);
         jh.join().unwrap();
     }
+
+    #[test]
+    fn max_frame_length_rejects_oversized_sync_frame() {
+        let mut codec = BinCodec::<Vec<u8>>::new().max_frame_length(4);
+        let mut buf = BytesMut::from(&b"too many bytes"[..]);
+        assert!(codec.decode(&mut buf).is_err());
+    }
+
+    #[test]
+    fn max_frame_length_accepts_pipelined_sync_frames() {
+        // Two legitimate, individually tiny messages delivered in the same
+        // buffer must not be rejected just because their combined backlog
+        // exceeds `max_frame_length` — only a single oversized message may
+        // be rejected.
+        let mut encoder = BinCodec::<bool>::new();
+        let mut buf = BytesMut::new();
+        encoder.encode(true, &mut buf).unwrap();
+        encoder.encode(false, &mut buf).unwrap();
+
+        let mut codec = BinCodec::<bool>::new().max_frame_length(1);
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(true));
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(false));
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn max_frame_length_rejects_oversized_async_frame() {
+        let mut codec = BinCodec::<Vec<u8>>::new().for_async().max_frame_length(4);
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(&100u32.to_be_bytes());
+        assert!(codec.decode(&mut buf).is_err());
+    }
+
+    #[test]
+    fn max_frame_length_rejects_oversized_chunked_frame() {
+        let mut codec = BinCodec::<Vec<u8>>::new().for_chunked().max_frame_length(4);
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(&0u32.to_be_bytes()); // message id
+        buf.extend_from_slice(&0u32.to_be_bytes()); // chunk index
+        buf.extend_from_slice(&[1]); // final
+        buf.extend_from_slice(&100u32.to_be_bytes()); // chunk payload length
+        assert!(codec.decode(&mut buf).is_err());
+    }
+
+    #[test]
+    fn max_frame_length_rejects_oversized_chunked_reassembly() {
+        let mut codec = BinCodec::<Vec<u8>>::new().for_chunked().max_frame_length(4);
+
+        let mut first = BytesMut::new();
+        first.extend_from_slice(&0u32.to_be_bytes()); // message id
+        first.extend_from_slice(&0u32.to_be_bytes()); // chunk index
+        first.extend_from_slice(&[0]); // not the final chunk
+        first.extend_from_slice(&3u32.to_be_bytes()); // chunk payload length
+        first.extend_from_slice(&[0, 0, 0]);
+        assert!(codec.decode(&mut first).unwrap().is_none(), "first chunk alone is within the limit");
+
+        let mut second = BytesMut::new();
+        second.extend_from_slice(&0u32.to_be_bytes()); // same message id
+        second.extend_from_slice(&1u32.to_be_bytes()); // next chunk index
+        second.extend_from_slice(&[0]); // still not final
+        second.extend_from_slice(&3u32.to_be_bytes()); // chunk payload length
+        second.extend_from_slice(&[0, 0, 0]);
+        assert!(
+            codec.decode(&mut second).is_err(),
+            "second chunk pushes the reassembled total over the limit"
+        );
+    }
 }